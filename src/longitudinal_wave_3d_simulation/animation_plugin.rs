@@ -2,13 +2,36 @@ use std::f32::consts::{PI, TAU};
 
 use bevy::prelude::*;
 use bevy::time::Stopwatch;
-use bevy_rapier3d::prelude::*;
+use bevy::utils::HashMap;
+use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 
 use crate::pan_orbit_camera::{update_pan_orbit_camera, PanOrbitCamera};
 use crate::{AppCamera, AppState};
 
 use super::{LongitudinalWave3dSimulationParameters, UiEvents};
 
+/// Nearest-neighbour offsets connecting each particle to its lattice springs
+/// along the x/y/z axes.
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::new(1, 0, 0),
+    IVec3::new(-1, 0, 0),
+    IVec3::new(0, 1, 0),
+    IVec3::new(0, -1, 0),
+    IVec3::new(0, 0, 1),
+    IVec3::new(0, 0, -1),
+];
+
+/// Which model animates the 3D lattice. Only one runs at a time so the spheres
+/// aren't driven by two unrelated waves at once: the spring-mass Verlet solver
+/// (this plugin) or the scalar finite-difference field (the simulation plugin).
+#[derive(Clone, Copy, Default, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Resource)]
+pub(crate) enum Wave3dModel {
+    #[default]
+    SpringMass,
+    ScalarField,
+}
+
 #[derive(Default, Resource)]
 struct Entities(Vec<Entity>);
 
@@ -16,8 +39,14 @@ struct Entities(Vec<Entity>);
 struct AnimationTimer(Stopwatch);
 
 #[derive(Component)]
-struct Particle {
+pub(crate) struct Particle {
     initial_translation: Vec3,
+    /// Integer lattice coordinate, used to find spring neighbours and, by the
+    /// 3D wave-field plugin, to sample the pressure field at this particle.
+    pub(crate) grid: IVec3,
+    velocity: Vec3,
+    /// Acceleration carried between the two velocity-Verlet half steps.
+    acceleration: Vec3,
 }
 
 #[derive(Component)]
@@ -28,7 +57,10 @@ pub struct AnimationPlugin;
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Entities::default())
+            .insert_resource(Wave3dModel::default())
             .insert_resource(AnimationTimer(Stopwatch::new()))
+            .register_type::<Wave3dModel>()
+            .add_plugin(ResourceInspectorPlugin::<Wave3dModel>::default())
             .add_system_set(
                 SystemSet::on_enter(AppState::LongitudinalWaveSimulation3d)
                     .with_system(setup),
@@ -37,7 +69,7 @@ impl Plugin for AnimationPlugin {
                 SystemSet::on_update(AppState::LongitudinalWaveSimulation3d)
                     .with_system(update_pan_orbit_camera)
                     .with_system(apply_impulse)
-                    .with_system(apply_equilibrium_force)
+                    .with_system(integrate_lattice)
                     .with_system(on_ui_events),
             )
             .add_system_set(
@@ -57,12 +89,7 @@ fn setup(
     mut materials: ResMut<Assets<StandardMaterial>>,
     parameters: Res<LongitudinalWave3dSimulationParameters>,
     mut entities: ResMut<Entities>,
-    mut rapier_debug_config: ResMut<DebugRenderContext>,
-    mut rapier_config: ResMut<RapierConfiguration>,
 ) {
-    rapier_debug_config.enabled = true;
-    rapier_config.gravity = Vec3::ZERO;
-
     mouse_button.reset_all();
 
     time.pause();
@@ -73,21 +100,18 @@ fn setup(
 
     let max_x_z = parameters.dimx.max(parameters.dimz) as f32 * 2.0;
 
-    let plane = commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Plane {
-                size: max_x_z * 2.0,
-            })),
-            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-            transform: Transform::from_xyz(
-                parameters.dimx as f32 / 2.0,
-                -2.0,
-                parameters.dimz as f32 / 2.0,
-            ),
-            ..default()
-        },
-        Collider::cuboid(max_x_z, 0.1, max_x_z),
-    ));
+    let plane = commands.spawn(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane {
+            size: max_x_z * 2.0,
+        })),
+        material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+        transform: Transform::from_xyz(
+            parameters.dimx as f32 / 2.0,
+            -2.0,
+            parameters.dimz as f32 / 2.0,
+        ),
+        ..default()
+    });
 
     entities.0.push(plane.id());
 
@@ -148,23 +172,26 @@ fn initialize_spheres(
         subdivisions: 6,
     }));
 
-    let material1_handle = materials.add(Color::rgb(0.6, 0.6, 0.6).into());
-    let material2_handle = materials.add(Color::rgb(0.7, 0.5, 0.5).into());
-
     for x in 0..parameters.dimx {
         for y in 0..parameters.dimy {
             for z in 0..parameters.dimz {
-                let material = if z == 0 {
-                    material2_handle.clone()
+                // Each particle owns its material so the 3D wave-field plugin
+                // can recolour it per voxel without affecting its neighbours.
+                let base_color = if z == 0 {
+                    Color::rgb(0.7, 0.5, 0.5)
                 } else {
-                    material1_handle.clone()
+                    Color::rgb(0.6, 0.6, 0.6)
                 };
+                let material = materials.add(base_color.into());
 
                 let translation = Vec3::new(x as f32, y as f32, z as f32);
 
                 let mut particle = commands.spawn((
                     Particle {
                         initial_translation: translation,
+                        grid: IVec3::new(x as i32, y as i32, z as i32),
+                        velocity: Vec3::ZERO,
+                        acceleration: Vec3::ZERO,
                     },
                     PbrBundle {
                         mesh: mesh.clone(),
@@ -172,17 +199,12 @@ fn initialize_spheres(
                         transform: Transform::from_translation(translation),
                         ..default()
                     },
-                    Collider::ball(parameters.radius),
-                    Restitution::coefficient(0.7),
-                    ExternalImpulse::default(),
-                    ExternalForce::default(),
                 ));
 
+                // The `z == 0` plane is driven/fixed boundary; it follows the
+                // sinusoidal forcing instead of being integrated.
                 if z == 0 {
                     particle.insert(ApplyingForce);
-                    particle.insert(RigidBody::Fixed);
-                } else {
-                    particle.insert(RigidBody::Dynamic);
                 }
 
                 entities.0.push(particle.id());
@@ -192,37 +214,116 @@ fn initialize_spheres(
 }
 
 fn apply_impulse(
+    model: Res<Wave3dModel>,
     time: Res<Time>,
     mut animation_timer: ResMut<AnimationTimer>,
     mut force_sources: Query<
-        (&Particle, &mut ExternalImpulse, &mut Transform),
+        (&Particle, &mut Transform),
         With<ApplyingForce>,
     >,
     parameters: Res<LongitudinalWave3dSimulationParameters>,
 ) {
+    if *model != Wave3dModel::SpringMass {
+        return;
+    }
+
     animation_timer.0.tick(time.delta());
 
     let elapsed = animation_timer.0.elapsed();
     let z =
         (elapsed.as_secs_f32() * parameters.applying_force_freq * TAU).sin();
 
-    for (particle, _, mut transform) in force_sources.iter_mut() {
+    for (particle, mut transform) in force_sources.iter_mut() {
         transform.translation.z = particle.initial_translation.z
             + (z * parameters.applying_force_factor);
     }
 }
 
-fn apply_equilibrium_force(
-    mut force_sources: Query<(&Particle, &Transform, &mut ExternalForce)>,
+/// Advance the free lattice particles one velocity-Verlet step.
+///
+/// Each particle is connected to its nearest neighbours along x/y/z by a
+/// Hookean spring of rest length equal to the grid spacing, and the driven
+/// `z == 0` plane acts as a moving boundary condition. The update is the
+/// standard velocity-Verlet scheme:
+///
+/// ```text
+/// pos += v*dt + 0.5*a*dt^2
+/// a_new = F(new pos) / m
+/// v += 0.5*(a + a_new)*dt
+/// ```
+fn integrate_lattice(
+    model: Res<Wave3dModel>,
     parameters: Res<LongitudinalWave3dSimulationParameters>,
+    mut particles: Query<(&mut Particle, &mut Transform, Option<&ApplyingForce>)>,
 ) {
-    for (particle, transform, mut external_force) in force_sources.iter_mut() {
-        let equilizing_force_direction =
-            particle.initial_translation - transform.translation;
+    if *model != Wave3dModel::SpringMass {
+        return;
+    }
+
+    let dt = parameters.time_step_width;
+    let k = parameters.spring_constant;
+    let c = parameters.damping;
+    let m = parameters.particle_mass;
+    // Particles are laid out one unit apart, so the spring rest length is 1.
+    let rest_len = 1.0;
+
+    // First half step: move free particles from their stored acceleration.
+    for (mut particle, mut transform, driven) in particles.iter_mut() {
+        if driven.is_some() {
+            continue;
+        }
+        let displacement = particle.velocity * dt
+            + 0.5 * particle.acceleration * dt * dt;
+        transform.translation += displacement;
+    }
+
+    // Snapshot the new positions (including the driven boundary) so spring
+    // forces are evaluated at the post-move configuration.
+    let positions: HashMap<IVec3, Vec3> = particles
+        .iter()
+        .map(|(particle, transform, _)| (particle.grid, transform.translation))
+        .collect();
+
+    // Second half step: recompute the acceleration and finish the velocity.
+    for (mut particle, _, driven) in particles.iter_mut() {
+        if driven.is_some() {
+            continue;
+        }
+        let pos = positions[&particle.grid];
+        let force =
+            spring_force(particle.grid, pos, &positions, k, rest_len)
+                - c * particle.velocity;
+        let acceleration = force / m;
+
+        particle.velocity +=
+            0.5 * (particle.acceleration + acceleration) * dt;
+        particle.acceleration = acceleration;
+    }
+}
 
-        external_force.force =
-            equilizing_force_direction * parameters.equilibrium_force_factor;
+/// Sum the Hookean forces on the particle at `grid`/`pos` from its present
+/// lattice neighbours: `-k*(current_len - rest_len)*dir` per spring.
+fn spring_force(
+    grid: IVec3,
+    pos: Vec3,
+    positions: &HashMap<IVec3, Vec3>,
+    k: f32,
+    rest_len: f32,
+) -> Vec3 {
+    let mut force = Vec3::ZERO;
+
+    for offset in NEIGHBORS {
+        if let Some(neighbor) = positions.get(&(grid + offset)) {
+            let delta = pos - *neighbor;
+            let current_len = delta.length();
+            if current_len > f32::EPSILON {
+                let dir = delta / current_len;
+                force += -k * (current_len - rest_len) * dir;
+            }
+        }
     }
+
+    force
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -264,18 +365,43 @@ fn on_ui_events(
     }
 }
 
-fn cleanup(
-    mut commands: Commands,
-    mut entities: ResMut<Entities>,
-    mut rapier_debug_config: ResMut<DebugRenderContext>,
-    mut rapier_config: ResMut<RapierConfiguration>,
-) {
+fn cleanup(mut commands: Commands, mut entities: ResMut<Entities>) {
     for entity in entities.0.drain(..) {
         if let Some(mut entity) = commands.get_entity(entity) {
             entity.despawn();
         }
     }
+}
 
-    *rapier_debug_config = DebugRenderContext::default();
-    *rapier_config = RapierConfiguration::default();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spring_force_vanishes_at_rest_length() {
+        let grid = IVec3::ZERO;
+        let pos = Vec3::ZERO;
+        let mut positions = HashMap::new();
+        positions.insert(grid, pos);
+        positions.insert(IVec3::new(1, 0, 0), Vec3::new(1.0, 0.0, 0.0));
+
+        let force = spring_force(grid, pos, &positions, 10.0, 1.0);
+
+        assert!(force.length() < 1e-6, "rest spring should be slack: {force:?}");
+    }
+
+    #[test]
+    fn spring_force_pushes_back_when_compressed() {
+        let grid = IVec3::ZERO;
+        let pos = Vec3::ZERO;
+        let mut positions = HashMap::new();
+        positions.insert(grid, pos);
+        positions.insert(IVec3::new(1, 0, 0), Vec3::new(0.5, 0.0, 0.0));
+
+        let force = spring_force(grid, pos, &positions, 10.0, 1.0);
+
+        // Compressed by 0.5 along +x, so the restoring force points along -x.
+        assert!((force.x + 5.0).abs() < 1e-5, "{force:?}");
+        assert!(force.y.abs() < 1e-6 && force.z.abs() < 1e-6);
+    }
 }
@@ -0,0 +1,205 @@
+use ndarray::prelude::*;
+
+/// Advance the interior of the `(dimx, dimy, dimz)` pressure field one step
+/// with the second-order accurate 7-point Laplacian
+/// `u[x±1] + u[y±1] + u[z±1] - 6u`.
+///
+/// Mirrors the 2D `update_with_laplace_operator_1`: the full time-sliced field
+/// `u` is read with slice 1 as the current and slice 2 as the previous frame,
+/// and the returned array is the interior to be written back into slice 0.
+pub fn update_with_laplace_operator_1(
+    _dimx: usize,
+    _dimy: usize,
+    _dimz: usize,
+    tau: &Array3<f32>,
+    u: &Array4<f32>,
+) -> Array3<f32> {
+    let current = u.slice(s![1, .., .., ..]);
+    let previous = u.slice(s![2, .., .., ..]);
+
+    let laplace = &current.slice(s![..-2, 1..-1, 1..-1])
+        + &current.slice(s![2.., 1..-1, 1..-1])
+        + &current.slice(s![1..-1, ..-2, 1..-1])
+        + &current.slice(s![1..-1, 2.., 1..-1])
+        + &current.slice(s![1..-1, 1..-1, ..-2])
+        + &current.slice(s![1..-1, 1..-1, 2..])
+        - 6.0 * &current.slice(s![1..-1, 1..-1, 1..-1]);
+
+    2.0 * &current.slice(s![1..-1, 1..-1, 1..-1])
+        - &previous.slice(s![1..-1, 1..-1, 1..-1])
+        + &tau.slice(s![1..-1, 1..-1, 1..-1]) * &laplace
+}
+
+/// Fourth-order accurate variant using a 13-point stencil: the centre plus
+/// the `±1` and `±2` samples along each axis, for a boundary of size 4.
+/// Diagonal neighbours are not sampled.
+pub fn update_with_laplace_operator_4(
+    _dimx: usize,
+    _dimy: usize,
+    _dimz: usize,
+    tau: &Array3<f32>,
+    u: &Array4<f32>,
+) -> Array3<f32> {
+    let c = u.slice(s![1, .., .., ..]);
+    let previous = u.slice(s![2, .., .., ..]);
+
+    // -1/12, 4/3, -5/2, 4/3, -1/12 central coefficients, summed per axis.
+    let axis = |m2: ArrayView3<f32>,
+                m1: ArrayView3<f32>,
+                c0: ArrayView3<f32>,
+                p1: ArrayView3<f32>,
+                p2: ArrayView3<f32>| {
+        -1.0 / 12.0 * &m2 + 4.0 / 3.0 * &m1 - 5.0 / 2.0 * &c0
+            + 4.0 / 3.0 * &p1
+            - 1.0 / 12.0 * &p2
+    };
+
+    let inner = s![4..-4, 4..-4, 4..-4];
+
+    let laplace = axis(
+        c.slice(s![2..-6, 4..-4, 4..-4]),
+        c.slice(s![3..-5, 4..-4, 4..-4]),
+        c.slice(inner),
+        c.slice(s![5..-3, 4..-4, 4..-4]),
+        c.slice(s![6..-2, 4..-4, 4..-4]),
+    ) + axis(
+        c.slice(s![4..-4, 2..-6, 4..-4]),
+        c.slice(s![4..-4, 3..-5, 4..-4]),
+        c.slice(inner),
+        c.slice(s![4..-4, 5..-3, 4..-4]),
+        c.slice(s![4..-4, 6..-2, 4..-4]),
+    ) + axis(
+        c.slice(s![4..-4, 4..-4, 2..-6]),
+        c.slice(s![4..-4, 4..-4, 3..-5]),
+        c.slice(inner),
+        c.slice(s![4..-4, 4..-4, 5..-3]),
+        c.slice(s![4..-4, 4..-4, 6..-2]),
+    );
+
+    2.0 * &c.slice(inner) - &previous.slice(inner)
+        + &tau.slice(inner) * &laplace
+}
+
+/// Mur first-order absorbing boundary condition applied to the six faces of
+/// the cube, ported from the 2D four-edge version.
+///
+/// Each face is a plane, so we iterate only the two in-plane axes and relax
+/// each cell of the face toward its inward neighbour. Edge and corner cells,
+/// which lie on two or three faces, are relaxed once per face.
+pub fn update_with_absorbing_boundary(
+    dimx: usize,
+    dimy: usize,
+    dimz: usize,
+    boundary_size: usize,
+    kappa: &Array3<f32>,
+    u: &mut Array4<f32>,
+) {
+    let b = boundary_size;
+
+    // x = low / high faces
+    for y in 0..dimy {
+        for z in 0..dimz {
+            relax(u, kappa, (b - 1, y, z), (b, y, z));
+            relax(u, kappa, (dimx - b, y, z), (dimx - b - 1, y, z));
+        }
+    }
+
+    // y = low / high faces
+    for x in 0..dimx {
+        for z in 0..dimz {
+            relax(u, kappa, (x, b - 1, z), (x, b, z));
+            relax(u, kappa, (x, dimy - b, z), (x, dimy - b - 1, z));
+        }
+    }
+
+    // z = low / high faces
+    for x in 0..dimx {
+        for y in 0..dimy {
+            relax(u, kappa, (x, y, b - 1), (x, y, b));
+            relax(u, kappa, (x, y, dimz - b), (x, y, dimz - b - 1));
+        }
+    }
+}
+
+/// Relax a single boundary cell toward the adjacent interior layer with the
+/// local Courant factor `kappa`, so outgoing pressure waves leave with little
+/// reflection.
+fn relax(
+    u: &mut Array4<f32>,
+    kappa: &Array3<f32>,
+    face: (usize, usize, usize),
+    neighbor: (usize, usize, usize),
+) {
+    let current = u[[0, face.0, face.1, face.2]];
+    let inner = u[[1, neighbor.0, neighbor.1, neighbor.2]];
+    let factor = kappa[[face.0, face.1, face.2]];
+    u[[0, face.0, face.1, face.2]] = current + factor * (inner - current);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Zip;
+
+    /// Build `(3, dim, dim, dim)` with the given current/previous slice fills.
+    fn field(dim: usize, current: f32, previous: f32) -> Array4<f32> {
+        let mut u = Array4::zeros((3, dim, dim, dim));
+        u.slice_mut(s![1, .., .., ..]).fill(current);
+        u.slice_mut(s![2, .., .., ..]).fill(previous);
+        u
+    }
+
+    #[test]
+    fn operator_1_returns_boundary_1_interior() {
+        let dim = 6;
+        let u = field(dim, 1.0, 0.5);
+        let tau = Array3::from_elem((dim, dim, dim), 0.25);
+
+        let out = update_with_laplace_operator_1(dim, dim, dim, &tau, &u);
+
+        // Boundary 1 trims one cell on each side of every axis.
+        assert_eq!(out.dim(), (dim - 2, dim - 2, dim - 2));
+        // Laplacian of a constant field is zero, so new = 2*current - previous.
+        for &v in out.iter() {
+            assert!((v - 1.5).abs() < 1e-6, "{v}");
+        }
+    }
+
+    #[test]
+    fn operator_4_returns_boundary_4_interior() {
+        let dim = 10;
+        let u = field(dim, 1.0, 0.5);
+        let tau = Array3::from_elem((dim, dim, dim), 0.25);
+
+        let out = update_with_laplace_operator_4(dim, dim, dim, &tau, &u);
+
+        // The 4th-order variant runs with a boundary of 4, trimming four
+        // cells on each side of every axis.
+        assert_eq!(out.dim(), (dim - 8, dim - 8, dim - 8));
+        for &v in out.iter() {
+            assert!((v - 1.5).abs() < 1e-6, "{v}");
+        }
+    }
+
+    #[test]
+    fn slice_swap_rotates_the_time_buffers() {
+        // Mirror the buffer rotation performed in `update_wave` before the
+        // stencil runs: slice 0 (new) and slice 1 (current) move back in time.
+        let dim = 3;
+        let mut u = Array4::zeros((3, dim, dim, dim));
+        u.slice_mut(s![0, .., .., ..]).fill(3.0);
+        u.slice_mut(s![1, .., .., ..]).fill(2.0);
+        u.slice_mut(s![2, .., .., ..]).fill(1.0);
+
+        let (u_2, mut u_1, u_0) = u.multi_slice_mut((
+            s![2, .., .., ..],
+            s![1, .., .., ..],
+            s![0, .., .., ..],
+        ));
+        Zip::from(u_2).and(&mut u_1).for_each(std::mem::swap);
+        Zip::from(u_1).and(u_0).for_each(std::mem::swap);
+
+        assert_eq!(u[[1, 0, 0, 0]], 3.0);
+        assert_eq!(u[[2, 0, 0, 0]], 2.0);
+    }
+}
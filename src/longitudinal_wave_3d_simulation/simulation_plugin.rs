@@ -1,16 +1,206 @@
 use bevy::prelude::*;
+use ndarray::prelude::*;
+use ndarray::Zip;
 
 use crate::AppState;
 
+use super::animation_plugin::{Particle, Wave3dModel};
+use super::finite_difference::{
+    update_with_absorbing_boundary, update_with_laplace_operator_1,
+    update_with_laplace_operator_4,
+};
+use super::LongitudinalWave3dSimulationParameters;
+
+/// Three time slices of the `(dimx, dimy, dimz)` scalar pressure field,
+/// stored as `(slice, x, y, z)`; the volumetric analogue of the 2D
+/// `SimulationGrid`.
+#[derive(Default, Resource)]
+pub struct SimulationGrid3d(pub Array4<f32>);
+
+/// Per-cell Laplace-operator factor `(c*dt/dx)^2` for the wave equation.
+#[derive(Default, Resource)]
+struct Tau(Array3<f32>);
+
+/// Per-cell factor `dt*c/dx` for the absorbing boundary condition.
+#[derive(Default, Resource)]
+struct Kappa(Array3<f32>);
+
+/// Frames re-driven between source pulses: the centre point source fires on
+/// every `SOURCE_PERIOD_FRAMES`th frame instead of being clamped every frame.
+const SOURCE_PERIOD_FRAMES: u32 = 120;
+
+/// Frame counter driving the periodic point source.
+#[derive(Default, Resource)]
+struct FrameCount(u32);
+
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_enter(AppState::LongitudinalWaveSimulation3d)
-                .with_system(setup),
+        app.insert_resource(SimulationGrid3d::default())
+            .insert_resource(Tau::default())
+            .insert_resource(Kappa::default())
+            .insert_resource(FrameCount::default())
+            .add_system_set(
+                SystemSet::on_enter(AppState::LongitudinalWaveSimulation3d)
+                    .with_system(setup),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::LongitudinalWaveSimulation3d)
+                    .with_system(apply_force)
+                    .with_system(update_wave)
+                    .with_system(render_volume),
+            );
+    }
+}
+
+fn setup(
+    mut tau: ResMut<Tau>,
+    mut kappa: ResMut<Kappa>,
+    mut u: ResMut<SimulationGrid3d>,
+    parameters: Res<LongitudinalWave3dSimulationParameters>,
+) {
+    let (dimx, dimy, dimz) =
+        (parameters.dimx, parameters.dimy, parameters.dimz);
+
+    // Validate up front rather than panicking in the per-frame `update_wave`.
+    if !matches!(parameters.boundary_size, 1 | 4) {
+        warn!(
+            "unsupported boundary_size {}; only 1 and 4 are implemented, the \
+             volumetric update will be skipped",
+            parameters.boundary_size
+        );
+    }
+
+    tau.0 = Array3::from_elem(
+        (dimx, dimy, dimz),
+        ((parameters.wave_velocity * parameters.time_step_width)
+            / parameters.spatial_step_width)
+            .powi(2),
+    );
+
+    kappa.0 = Array3::from_elem(
+        (dimx, dimy, dimz),
+        parameters.time_step_width * parameters.wave_velocity
+            / parameters.spatial_step_width,
+    );
+
+    u.0 = Array4::zeros((3, dimx, dimy, dimz));
+}
+
+fn apply_force(
+    model: Res<Wave3dModel>,
+    mut frame: ResMut<FrameCount>,
+    mut u: ResMut<SimulationGrid3d>,
+    parameters: Res<LongitudinalWave3dSimulationParameters>,
+) {
+    if *model != Wave3dModel::ScalarField {
+        return;
+    }
+
+    frame.0 = frame.0.wrapping_add(1);
+
+    // A point source at the centre of one face, analogous to the 2D
+    // `apply_force` seed. Fire it as a periodic pulse rather than clamping
+    // the cell every frame, which would pin the field and never radiate.
+    if frame.0 % SOURCE_PERIOD_FRAMES != 0 {
+        return;
+    }
+
+    let init_x = parameters.dimx / 2;
+    let init_y = parameters.dimy / 2;
+
+    *u.0.get_mut((0, init_x, init_y, 0)).unwrap() =
+        parameters.applied_force_amplitude;
+}
+
+fn update_wave(
+    model: Res<Wave3dModel>,
+    mut u: ResMut<SimulationGrid3d>,
+    tau: Res<Tau>,
+    kappa: Res<Kappa>,
+    parameters: Res<LongitudinalWave3dSimulationParameters>,
+) {
+    if *model != Wave3dModel::ScalarField {
+        return;
+    }
+
+    let boundary_size = parameters.boundary_size;
+    let (dimx, dimy, dimz) =
+        (parameters.dimx, parameters.dimy, parameters.dimz);
+
+    let (u_2, mut u_1, u_0) = u.0.multi_slice_mut((
+        s![2, .., .., ..],
+        s![1, .., .., ..],
+        s![0, .., .., ..],
+    ));
+
+    Zip::from(u_2).and(&mut u_1).for_each(std::mem::swap);
+    Zip::from(u_1).and(u_0).for_each(std::mem::swap);
+
+    // Unsupported sizes are reported once in `setup`; skip rather than panic.
+    let new_u = match boundary_size {
+        1 => update_with_laplace_operator_1(dimx, dimy, dimz, &tau.0, &u.0),
+        4 => update_with_laplace_operator_4(dimx, dimy, dimz, &tau.0, &u.0),
+        _ => return,
+    };
+
+    u.0.slice_mut(s![
+        0,
+        boundary_size..(dimx - boundary_size),
+        boundary_size..(dimy - boundary_size),
+        boundary_size..(dimz - boundary_size)
+    ])
+    .assign(&new_u);
+
+    if parameters.use_absorbing_boundary {
+        update_with_absorbing_boundary(
+            dimx,
+            dimy,
+            dimz,
+            boundary_size,
+            &kappa.0,
+            &mut u.0,
         );
+    } else {
+        u.0.mapv_inplace(|u| u * 0.995);
     }
 }
 
-fn setup() {}
+/// Drive the existing particle lattice from the solved scalar field: each
+/// particle is displaced longitudinally (along z) by the local amplitude and
+/// tinted by its sign, using its grid coordinate as the sampling point. This
+/// is the scalar-field counterpart of the spring-mass animation, and only one
+/// of the two runs at a time (see [`Wave3dModel`]).
+fn render_volume(
+    model: Res<Wave3dModel>,
+    u: Res<SimulationGrid3d>,
+    parameters: Res<LongitudinalWave3dSimulationParameters>,
+    mut particles: Query<(&Particle, &mut Transform, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if *model != Wave3dModel::ScalarField {
+        return;
+    }
+
+    for (particle, mut transform, material_handle) in particles.iter_mut() {
+        let IVec3 { x, y, z } = particle.grid;
+        let Some(&amplitude) =
+            u.0.get((0, x as usize, y as usize, z as usize))
+        else {
+            continue;
+        };
+
+        // Longitudinal displacement from the rest position at the grid node.
+        transform.translation = particle.grid.as_vec3()
+            + Vec3::Z * amplitude * parameters.applying_force_factor;
+
+        if let Some(material) = materials.get_mut(material_handle) {
+            // Map signed amplitude onto colour (blue ↔ red) and opacity.
+            let t = (amplitude.tanh() + 1.0) / 2.0;
+            material.base_color =
+                Color::rgba(t, 0.0, 1.0 - t, amplitude.abs().tanh());
+            material.alpha_mode = AlphaMode::Blend;
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_ggrs::ggrs::{self, PlayerType, SessionBuilder};
+use bevy_ggrs::{GGRSPlugin, Session};
+use bytemuck::{Pod, Zeroable};
+
+use super::animation_plugin::PlotClickedEvent;
+use super::SimulationParameters;
+use crate::SimulationGrid;
+
+/// Port the peer-to-peer socket binds to by default.
+const DEFAULT_PORT: u16 = 7070;
+/// Frames simulated per second in the rollback schedule.
+const FPS: usize = 60;
+
+/// The per-frame input that is serialized, exchanged and rolled back for every
+/// player: the grid cell a player clicked plus a "source pressed" bit.
+///
+/// It must be [`Pod`] so GGRS can treat it as a flat byte buffer; the packing
+/// mirrors the bounds check in [`super::simulation_plugin::apply_force`].
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
+pub struct WaveInput {
+    pub x: u16,
+    pub y: u16,
+    pub pressed: u8,
+    _pad: [u8; 3],
+}
+
+impl WaveInput {
+    pub fn pressed(x: usize, y: usize) -> Self {
+        Self {
+            x: x as u16,
+            y: y as u16,
+            pressed: 1,
+            _pad: [0; 3],
+        }
+    }
+}
+
+/// GGRS session configuration for the shared wave field.
+#[derive(Debug)]
+pub struct GgrsConfig;
+
+impl ggrs::Config for GgrsConfig {
+    type Input = WaveInput;
+    type State = u8;
+    type Address = std::net::SocketAddr;
+}
+
+/// Deterministic frame counter, advanced once per rollback frame and part of
+/// the rollback save-state so the periodic source seeds identically on every
+/// peer (no wall-clock `Time` dependence in the numeric update).
+#[derive(Clone, Copy, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct FrameCount(pub u32);
+
+/// Whether this peer hosts the session or joins a remote one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum SessionMode {
+    Host,
+    Join,
+}
+
+/// How this peer joins the shared field, filled in on the setup screen.
+#[derive(Clone, Debug, Resource, Reflect)]
+pub struct SessionConfig {
+    pub mode: SessionMode,
+    pub local_port: u16,
+    pub num_players: usize,
+    /// This peer's player handle.
+    pub local_handle: usize,
+    /// Socket addresses of the remote players, in handle order.
+    pub remotes: Vec<std::net::SocketAddr>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            mode: SessionMode::Host,
+            local_port: DEFAULT_PORT,
+            num_players: 2,
+            local_handle: 0,
+            remotes: Vec::new(),
+        }
+    }
+}
+
+/// The grid cell this peer clicked this frame, consumed by `read_local_input`.
+#[derive(Default, Resource)]
+pub struct LocalClick(pub Option<(usize, usize)>);
+
+/// Raised by the setup screen to request a connection on the next frame.
+#[derive(Default, Resource)]
+pub struct PendingConnect(pub bool);
+
+/// Editable text buffers backing the setup screen.
+#[derive(Resource)]
+pub struct SetupScreen {
+    port: String,
+    num_players: String,
+    local_handle: String,
+    remotes: String,
+}
+
+impl Default for SetupScreen {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT.to_string(),
+            num_players: "2".to_owned(),
+            local_handle: "0".to_owned(),
+            remotes: String::new(),
+        }
+    }
+}
+
+/// Register the rollback schedule and the rollback save-state.
+///
+/// The `update_wave` step is a pure function of [`SimulationGrid`] and
+/// [`SimulationParameters`], and the only nondeterministic input is the click,
+/// so running it inside the fixed GGRS schedule makes the field lockstep-safe.
+/// The three time slices in `SimulationGrid` and the [`FrameCount`] are the
+/// rollback save-state (cloned on save, overwritten on load).
+pub fn build(app: &mut App) {
+    // `register_rollback_resource::<SimulationGrid>` clones the full
+    // `Array3<f32>` on every save; enforce the `Clone` bound here so a missing
+    // derive is a compile error rather than a confusing trait-bound failure
+    // deep inside bevy_ggrs. The grid is only mutated inside the rollback
+    // stage (offline it runs in `on_update` but no rollback occurs there).
+    const fn assert_rollback_state<T: Clone + Resource>() {}
+    assert_rollback_state::<SimulationGrid>();
+
+    GGRSPlugin::<GgrsConfig>::new()
+        .with_update_frequency(FPS)
+        .with_input_system(read_local_input)
+        .register_rollback_resource::<SimulationGrid>()
+        .register_rollback_resource::<FrameCount>()
+        .build(app);
+
+    app.insert_resource(SessionConfig::default())
+        .insert_resource(LocalClick::default())
+        .insert_resource(PendingConnect::default())
+        .insert_resource(SetupScreen::default())
+        .insert_resource(FrameCount::default())
+        .register_type::<SessionConfig>()
+        .add_system_set_to_stage(
+            bevy_ggrs::ROLLBACK_DEFAULT,
+            SystemSet::new().with_system(advance_frame),
+        );
+}
+
+/// Advance the deterministic frame counter inside the rollback schedule.
+fn advance_frame(mut frame: ResMut<FrameCount>) {
+    frame.0 = frame.0.wrapping_add(1);
+}
+
+/// Sample this peer's local input once per rollback frame.
+///
+/// The click is resolved into a grid cell by [`fill_local_click`] and stored in
+/// [`LocalClick`]; here we only serialize it so GGRS can predict and, if
+/// needed, correct it on remote peers.
+pub fn read_local_input(
+    _handle: In<ggrs::PlayerHandle>,
+    local: Res<LocalClick>,
+    parameters: Res<SimulationParameters>,
+) -> WaveInput {
+    match local.0 {
+        Some((x, y)) if x < parameters.dimx && y < parameters.dimy => {
+            WaveInput::pressed(x, y)
+        }
+        _ => WaveInput::zeroed(),
+    }
+}
+
+/// Resolve this frame's `PlotClickedEvent` into a grid cell for GGRS.
+pub fn fill_local_click(
+    mut local: ResMut<LocalClick>,
+    mut plot_clicked_events: EventReader<PlotClickedEvent>,
+    parameters: Res<SimulationParameters>,
+) {
+    local.0 = None;
+    for event in plot_clicked_events.iter() {
+        let x = event.x.round() as usize;
+        let y = event.y.round() as usize;
+        if x < parameters.dimx && y < parameters.dimy {
+            local.0 = Some((x, y));
+        }
+    }
+}
+
+/// Host/join setup screen: edit the socket address, player count and handle,
+/// then press `Connect` to request a P2P session.
+pub fn session_setup_ui(
+    mut contexts: EguiContexts,
+    mut config: ResMut<SessionConfig>,
+    mut screen: ResMut<SetupScreen>,
+    mut pending: ResMut<PendingConnect>,
+    session: Option<Res<Session<GgrsConfig>>>,
+) {
+    if session.is_some() {
+        return;
+    }
+
+    egui::Window::new("Multiplayer").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut config.mode, SessionMode::Host, "Host");
+            ui.selectable_value(&mut config.mode, SessionMode::Join, "Join");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Local port");
+            ui.text_edit_singleline(&mut screen.port);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Players");
+            ui.text_edit_singleline(&mut screen.num_players);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Local handle");
+            ui.text_edit_singleline(&mut screen.local_handle);
+        });
+        ui.label("Remote addresses (one ip:port per line, in handle order)");
+        ui.text_edit_multiline(&mut screen.remotes);
+
+        if ui.button("Connect").clicked() {
+            apply_screen(&screen, &mut config);
+            pending.0 = true;
+        }
+    });
+}
+
+fn apply_screen(screen: &SetupScreen, config: &mut SessionConfig) {
+    config.local_port = screen.port.trim().parse().unwrap_or(DEFAULT_PORT);
+    config.num_players = screen.num_players.trim().parse().unwrap_or(2);
+    config.local_handle = screen.local_handle.trim().parse().unwrap_or(0);
+    config.remotes = screen
+        .remotes
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect();
+}
+
+/// Start the session once the setup screen has requested it.
+pub fn maybe_connect(
+    mut commands: Commands,
+    config: Res<SessionConfig>,
+    mut pending: ResMut<PendingConnect>,
+    session: Option<Res<Session<GgrsConfig>>>,
+) {
+    if !pending.0 || session.is_some() {
+        return;
+    }
+    pending.0 = false;
+
+    if let Err(error) = start_session(&mut commands, &config) {
+        error!("failed to start P2P session: {error}");
+    }
+}
+
+/// Build a peer-to-peer session from [`SessionConfig`].
+///
+/// Every player slot except this peer's own `local_handle` is a [`Remote`]
+/// taken from `remotes` (in handle order); the local peer binds `local_port`.
+///
+/// [`Remote`]: PlayerType::Remote
+fn start_session(
+    commands: &mut Commands,
+    config: &SessionConfig,
+) -> Result<(), ggrs::GgrsError> {
+    let mut builder = SessionBuilder::<GgrsConfig>::new()
+        .with_num_players(config.num_players)
+        .with_input_delay(2);
+
+    let mut remotes = config.remotes.iter();
+    for handle in 0..config.num_players {
+        let player = if handle == config.local_handle {
+            PlayerType::Local
+        } else {
+            let address = remotes
+                .next()
+                .copied()
+                .ok_or(ggrs::GgrsError::InvalidRequest {
+                    info: "not enough remote addresses for the player count"
+                        .to_owned(),
+                })?;
+            PlayerType::Remote(address)
+        };
+        builder = builder.add_player(player, handle)?;
+    }
+
+    let socket =
+        bevy_ggrs::ggrs::UdpNonBlockingSocket::bind_to_port(config.local_port)
+            .map_err(|_| ggrs::GgrsError::SocketCreationFailed)?;
+    let session = builder.start_p2p_session(socket)?;
+
+    commands.insert_resource(Session::P2PSession(session));
+    Ok(())
+}
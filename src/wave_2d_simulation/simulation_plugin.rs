@@ -9,9 +9,22 @@ use super::finite_difference::update_with_absorbing_boundary;
 use super::finite_difference::{
     update_with_laplace_operator_1, update_with_laplace_operator_4,
 };
+use super::network::{self, FrameCount, GgrsConfig};
+use bevy_ggrs::Session;
+use super::velocity_field::{
+    build_velocity_field, max_velocity, VelocitySource,
+};
 use super::SimulationGrid;
 use super::SimulationParameters;
 
+use bevy_ggrs::PlayerInputs;
+use bevy_inspector_egui::quick::ResourceInspectorPlugin;
+
+/// The Courant number for the 2D explicit scheme must not exceed `1/sqrt(2)`;
+/// above it the finite-difference update is unconditionally unstable and the
+/// field blows up silently. (The eventual 3D solver uses `1/sqrt(3)`.)
+const CFL_LIMIT_2D: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
 /// A field containing the factor for the Laplace Operator that
 /// combines Velocity and Grid Constants for the `Wave Equation`
 #[derive(Default, Resource)]
@@ -22,25 +35,65 @@ struct Tau(Array2<f32>);
 #[derive(Default, Resource)]
 struct Kappa(Array2<f32>);
 
-#[derive(Resource)]
-struct ApplyingForceTimer(Timer);
+/// Read-only CFL readout surfaced in the inspector so users can see the
+/// current Courant number and whether the configuration is stable.
+#[derive(Default, Resource, Reflect)]
+#[reflect(Resource)]
+struct CflStatus {
+    /// Current Courant number `C = v_max * dt / dx`.
+    courant_number: f32,
+    /// `C <= 1/sqrt(2)`.
+    stable: bool,
+}
 
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
-        let mut timer = Timer::default();
-        timer.pause();
+        // In a session the wave update is driven only by the fixed-timestep
+        // rollback schedule: any `Time`-delta dependence inside the numeric
+        // step would make re-simulation diverge from the confirmed history.
+        network::build(app);
 
         app.insert_resource(SimulationGrid::default())
             .insert_resource(Tau::default())
             .insert_resource(Kappa::default())
-            .insert_resource(ApplyingForceTimer(timer))
+            .insert_resource(VelocitySource::default())
+            .insert_resource(CflStatus::default())
+            // `SimulationParameters` is a reflected resource edited live in the
+            // inspector; changes are picked up by `recompute_fields`.
+            .register_type::<SimulationParameters>()
+            .register_type::<VelocitySource>()
+            .register_type::<CflStatus>()
+            .add_plugin(
+                ResourceInspectorPlugin::<SimulationParameters>::default(),
+            )
+            // Let users switch to `Noise`/`Image` at runtime so the lens and
+            // slow-inclusion demos can actually be enabled.
+            .add_plugin(
+                ResourceInspectorPlugin::<VelocitySource>::default(),
+            )
+            .add_plugin(ResourceInspectorPlugin::<CflStatus>::default())
             .add_system_set(
                 SystemSet::on_enter(AppState::Wave2dSimulation)
                     .with_system(setup),
             )
             .add_system_set(
+                SystemSet::on_update(AppState::Wave2dSimulation)
+                    .with_system(recompute_fields)
+                    .with_system(guard_cfl)
+                    .with_system(network::fill_local_click)
+                    .with_system(network::session_setup_ui)
+                    .with_system(network::maybe_connect)
+                    // Offline (no `Session`) the rollback stage never ticks, so
+                    // drive the simulation here instead. Each of these bails
+                    // out the moment a session exists.
+                    .with_system(advance_frame_standalone)
+                    .with_system(apply_force_standalone)
+                    .with_system(update_wave_standalone),
+            )
+            .add_system_set_to_stage(
+                bevy_ggrs::ROLLBACK_DEFAULT,
                 SystemSet::on_update(AppState::Wave2dSimulation)
                     .with_system(apply_force)
                     .with_system(update_wave),
@@ -52,32 +105,146 @@ fn setup(
     mut tau: ResMut<Tau>,
     mut kappa: ResMut<Kappa>,
     mut u: ResMut<SimulationGrid>,
+    velocity_source: Res<VelocitySource>,
     parameters: Res<SimulationParameters>,
 ) {
-    tau.0 = Array::from_elem(
-        (parameters.dimx, parameters.dimy),
-        ((parameters.wave_velocity * parameters.time_step_width)
-            / parameters.spatial_step_width)
-            .powi(2),
-    );
-
-    kappa.0 = Array2::from_elem(
-        (parameters.dimx, parameters.dimy),
-        parameters.time_step_width * parameters.wave_velocity
-            / parameters.spatial_step_width,
-    );
-
+    bake_fields(&mut tau, &mut kappa, &velocity_source, &parameters);
     u.0 = Array3::zeros((3, parameters.dimx, parameters.dimy));
 }
 
+/// Bake the `Tau`/`Kappa` factors from the (possibly heterogeneous) velocity
+/// field. Shared by `setup` and the live `recompute_fields` system so the two
+/// paths can never drift apart.
+fn bake_fields(
+    tau: &mut Tau,
+    kappa: &mut Kappa,
+    velocity_source: &VelocitySource,
+    parameters: &SimulationParameters,
+) {
+    // For `Homogeneous` this is simply `wave_velocity` in every cell,
+    // reproducing the original bake.
+    let velocity = build_velocity_field(velocity_source, parameters);
+
+    let dt = parameters.time_step_width;
+    let dx = parameters.spatial_step_width;
+
+    // `tau = (c*dt/dx)^2` and `kappa = dt*c/dx` per cell, so a slow-velocity
+    // inclusion or a lens simply shows up as lower `tau`/`kappa` there.
+    tau.0 = velocity.mapv(|c| ((c * dt) / dx).powi(2));
+    kappa.0 = velocity.mapv(|c| dt * c / dx);
+}
+
+/// Rebuild `Tau`/`Kappa` whenever the inspector edits `SimulationParameters`
+/// or the velocity source, so `wave_velocity`, `time_step_width`,
+/// `spatial_step_width` and friends take effect without a restart.
+fn recompute_fields(
+    mut tau: ResMut<Tau>,
+    mut kappa: ResMut<Kappa>,
+    velocity_source: Res<VelocitySource>,
+    parameters: Res<SimulationParameters>,
+) {
+    if parameters.is_changed() || velocity_source.is_changed() {
+        bake_fields(&mut tau, &mut kappa, &velocity_source, &parameters);
+    }
+}
+
+/// Surface the Courant number in the inspector and warn (and optionally clamp)
+/// when the configuration violates the CFL condition, so cranking the velocity
+/// no longer produces a silent numerical blow-up.
+///
+/// The bound uses the field's *maximum* velocity, so a heterogeneous field
+/// whose `v_max` exceeds `wave_velocity` is still caught.
+fn guard_cfl(
+    mut parameters: ResMut<SimulationParameters>,
+    velocity_source: Res<VelocitySource>,
+    mut status: ResMut<CflStatus>,
+) {
+    if !parameters.is_changed() && !velocity_source.is_changed() {
+        return;
+    }
+
+    let v_max = max_velocity(&velocity_source, &parameters);
+    let courant =
+        v_max * parameters.time_step_width / parameters.spatial_step_width;
+
+    status.courant_number = courant;
+    status.stable = courant <= CFL_LIMIT_2D;
+
+    if !status.stable {
+        warn!(
+            "CFL violated: C = {courant:.3} > {CFL_LIMIT_2D:.3}; the 2D scheme \
+             is unstable. Reduce velocity or time_step_width."
+        );
+
+        if parameters.clamp_time_step_to_cfl {
+            // Clamp `time_step_width` to the largest stable value.
+            parameters.time_step_width =
+                CFL_LIMIT_2D * parameters.spatial_step_width / v_max;
+        }
+    }
+}
+
+/// Advance the deterministic frame counter offline, mirroring the rollback
+/// stage's `advance_frame` so the periodic source still ticks single-user.
+fn advance_frame_standalone(
+    session: Option<Res<Session<GgrsConfig>>>,
+    mut frame: ResMut<FrameCount>,
+) {
+    if session.is_some() {
+        return;
+    }
+    frame.0 = frame.0.wrapping_add(1);
+}
+
+/// Rollback-stage source injection (confirmed GGRS inputs + periodic seed).
 fn apply_force(
-    time: Res<Time>,
-    mut timer: ResMut<ApplyingForceTimer>,
+    frame: Res<FrameCount>,
     mut u: ResMut<SimulationGrid>,
     parameters: Res<SimulationParameters>,
     mut plot_clicked_events: EventReader<PlotClickedEvent>,
+    inputs: Option<Res<PlayerInputs<GgrsConfig>>>,
+) {
+    let clicks = collect_clicks(&mut plot_clicked_events);
+    seed_field(frame.0, &mut u, &parameters, &clicks, inputs.as_deref());
+}
+
+/// Offline source injection; no GGRS inputs exist, so only clicks and the
+/// periodic seed apply.
+fn apply_force_standalone(
+    session: Option<Res<Session<GgrsConfig>>>,
+    frame: Res<FrameCount>,
+    mut u: ResMut<SimulationGrid>,
+    parameters: Res<SimulationParameters>,
+    mut plot_clicked_events: EventReader<PlotClickedEvent>,
+) {
+    if session.is_some() {
+        return;
+    }
+    let clicks = collect_clicks(&mut plot_clicked_events);
+    seed_field(frame.0, &mut u, &parameters, &clicks, None);
+}
+
+fn collect_clicks(
+    events: &mut EventReader<PlotClickedEvent>,
+) -> Vec<(usize, usize)> {
+    events
+        .iter()
+        .map(|event| (event.x.round() as usize, event.y.round() as usize))
+        .collect()
+}
+
+fn seed_field(
+    frame: u32,
+    u: &mut SimulationGrid,
+    parameters: &SimulationParameters,
+    clicks: &[(usize, usize)],
+    inputs: Option<&PlayerInputs<GgrsConfig>>,
 ) {
-    if timer.0.tick(time.delta()).just_finished() {
+    // Deterministic periodic source driven by the frame counter instead of
+    // `Time::delta`, so re-simulated frames reproduce it exactly.
+    if parameters.source_period_frames > 0
+        && frame % parameters.source_period_frames == 0
+    {
         let init_x = 4 * parameters.dimx / 6;
         let init_y = 4 * parameters.dimy / 6;
 
@@ -85,26 +252,61 @@ fn apply_force(
             parameters.applied_force_amplitude;
     }
 
-    for event in plot_clicked_events.iter() {
-        let event_x: usize = event.x.round() as usize;
-        let event_y: usize = event.y.round() as usize;
+    // In a networked session every peer injects the *confirmed* inputs of all
+    // players, so the field stays identical after rollback re-simulation. The
+    // raw `PlotClickedEvent` path is only used when running offline.
+    if let Some(inputs) = inputs {
+        for (input, _status) in inputs.iter() {
+            if input.pressed != 0 {
+                let x = input.x as usize;
+                let y = input.y as usize;
 
-        if 0 < event_x
-            && event_x < parameters.dimx
-            && 0 < event_y
-            && event_y < parameters.dimy
-        {
-            *u.0.get_mut((0, event_x, event_y)).unwrap() =
+                if 0 < x && x < parameters.dimx && 0 < y && y < parameters.dimy {
+                    *u.0.get_mut((0, x, y)).unwrap() =
+                        parameters.applied_force_amplitude;
+                }
+            }
+        }
+        return;
+    }
+
+    for &(x, y) in clicks {
+        if 0 < x && x < parameters.dimx && 0 < y && y < parameters.dimy {
+            *u.0.get_mut((0, x, y)).unwrap() =
                 parameters.applied_force_amplitude;
         }
     }
 }
 
+/// Rollback-stage wave step.
 fn update_wave(
     mut u: ResMut<SimulationGrid>,
     tau: Res<Tau>,
     kappa: Res<Kappa>,
     parameters: Res<SimulationParameters>,
+) {
+    step_wave(&mut u, &tau, &kappa, &parameters);
+}
+
+/// Offline wave step; runs only while no `Session` drives the rollback stage.
+fn update_wave_standalone(
+    session: Option<Res<Session<GgrsConfig>>>,
+    mut u: ResMut<SimulationGrid>,
+    tau: Res<Tau>,
+    kappa: Res<Kappa>,
+    parameters: Res<SimulationParameters>,
+) {
+    if session.is_some() {
+        return;
+    }
+    step_wave(&mut u, &tau, &kappa, &parameters);
+}
+
+fn step_wave(
+    u: &mut SimulationGrid,
+    tau: &Tau,
+    kappa: &Kappa,
+    parameters: &SimulationParameters,
 ) {
     let boundary_size = parameters.boundary_size;
 
@@ -152,3 +354,28 @@ fn update_wave(
         u.0.mapv_inplace(|u| u * 0.995);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cfl_limit_is_one_over_sqrt_two() {
+        assert_eq!(CFL_LIMIT_2D, std::f32::consts::FRAC_1_SQRT_2);
+    }
+
+    #[test]
+    fn courant_at_the_boundary_is_stable() {
+        // A configuration sitting exactly on the CFL limit is still stable;
+        // a hair above it is not. This mirrors `guard_cfl`'s classification.
+        let dx = 1.0_f32;
+        let dt = 0.5_f32;
+        let v_limit = CFL_LIMIT_2D * dx / dt;
+
+        let courant_at_limit = v_limit * dt / dx;
+        assert!(courant_at_limit <= CFL_LIMIT_2D);
+
+        let courant_above = (v_limit * 1.001) * dt / dx;
+        assert!(courant_above > CFL_LIMIT_2D);
+    }
+}
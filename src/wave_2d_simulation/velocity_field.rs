@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+use image::io::Reader as ImageReader;
+use ndarray::prelude::*;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use super::SimulationParameters;
+
+/// How the per-cell wave velocity field `c(x, y)` is produced before it is
+/// baked into the `Tau`/`Kappa` fields.
+///
+/// `Homogeneous` reproduces the original behaviour (a single constant
+/// `wave_velocity` in every cell); the other two variants fill the grid with a
+/// spatially varying velocity so lenses, waveguides and refraction become
+/// possible.
+#[derive(Clone, Debug, Reflect, Resource)]
+#[reflect(Resource)]
+pub enum VelocitySource {
+    /// A single constant velocity taken from `SimulationParameters::wave_velocity`.
+    Homogeneous,
+    /// Fractal Perlin noise remapped into `[v_min, v_max]`.
+    Noise {
+        seed: u32,
+        frequency: f64,
+        octaves: usize,
+        v_min: f32,
+        v_max: f32,
+    },
+    /// A velocity map painted in an image; pixel luminance is remapped into
+    /// `[v_min, v_max]`.
+    Image {
+        path: String,
+        v_min: f32,
+        v_max: f32,
+    },
+}
+
+impl Default for VelocitySource {
+    fn default() -> Self {
+        Self::Homogeneous
+    }
+}
+
+/// Build the `(dimx, dimy)` velocity field `c(x, y)` selected by `source`.
+///
+/// The finite-difference update already reads `tau` per element, so it is
+/// enough to bake a spatially varying velocity here; `setup` turns the result
+/// into the `Tau`/`Kappa` factors cell-by-cell.
+pub fn build_velocity_field(
+    source: &VelocitySource,
+    parameters: &SimulationParameters,
+) -> Array2<f32> {
+    match source {
+        VelocitySource::Homogeneous => Array2::from_elem(
+            (parameters.dimx, parameters.dimy),
+            parameters.wave_velocity,
+        ),
+        VelocitySource::Noise {
+            seed,
+            frequency,
+            octaves,
+            v_min,
+            v_max,
+        } => noise_field(
+            parameters.dimx,
+            parameters.dimy,
+            *seed,
+            *frequency,
+            *octaves,
+            *v_min,
+            *v_max,
+        ),
+        VelocitySource::Image {
+            path,
+            v_min,
+            v_max,
+        } => image_field(
+            parameters.dimx,
+            parameters.dimy,
+            path,
+            *v_min,
+            *v_max,
+        )
+        .unwrap_or_else(|| {
+            Array2::from_elem(
+                (parameters.dimx, parameters.dimy),
+                parameters.wave_velocity,
+            )
+        }),
+    }
+}
+
+/// The largest wave velocity the field can contain, used to bound the Courant
+/// number: for a heterogeneous field the fastest cell, not `wave_velocity`,
+/// governs stability.
+pub fn max_velocity(
+    source: &VelocitySource,
+    parameters: &SimulationParameters,
+) -> f32 {
+    match source {
+        VelocitySource::Homogeneous => parameters.wave_velocity,
+        VelocitySource::Noise { v_min, v_max, .. } => v_min.max(*v_max),
+        // An unreadable image falls back to `wave_velocity` in every cell, so
+        // take the maximum of the field actually built.
+        VelocitySource::Image { .. } => build_velocity_field(source, parameters)
+            .fold(f32::MIN, |acc, &c| acc.max(c)),
+    }
+}
+
+fn noise_field(
+    dimx: usize,
+    dimy: usize,
+    seed: u32,
+    frequency: f64,
+    octaves: usize,
+    v_min: f32,
+    v_max: f32,
+) -> Array2<f32> {
+    let fbm = Fbm::<Perlin>::new(seed)
+        .set_frequency(frequency)
+        .set_octaves(octaves);
+
+    Array2::from_shape_fn((dimx, dimy), |(x, y)| {
+        // `Fbm` returns roughly `[-1, 1]`; remap into `[v_min, v_max]`.
+        let n = fbm.get([x as f64, y as f64]) as f32;
+        remap(n, -1.0, 1.0, v_min, v_max)
+    })
+}
+
+fn image_field(
+    dimx: usize,
+    dimy: usize,
+    path: &str,
+    v_min: f32,
+    v_max: f32,
+) -> Option<Array2<f32>> {
+    let image = ImageReader::open(path).ok()?.decode().ok()?.to_luma8();
+    let (width, height) = image.dimensions();
+
+    Some(Array2::from_shape_fn((dimx, dimy), |(x, y)| {
+        // Sample the map with nearest-neighbour scaling so the grid size and
+        // the image size need not match.
+        let px = (x * width as usize / dimx).min(width as usize - 1);
+        let py = (y * height as usize / dimy).min(height as usize - 1);
+        let luma = image.get_pixel(px as u32, py as u32).0[0] as f32 / 255.0;
+        remap(luma, 0.0, 1.0, v_min, v_max)
+    }))
+}
+
+fn remap(value: f32, from_min: f32, from_max: f32, to_min: f32, to_max: f32) -> f32 {
+    let t = ((value - from_min) / (from_max - from_min)).clamp(0.0, 1.0);
+    to_min + t * (to_max - to_min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remap_hits_endpoints_and_midpoint() {
+        assert_eq!(remap(-1.0, -1.0, 1.0, 2.0, 6.0), 2.0);
+        assert_eq!(remap(1.0, -1.0, 1.0, 2.0, 6.0), 6.0);
+        assert_eq!(remap(0.0, -1.0, 1.0, 2.0, 6.0), 4.0);
+    }
+
+    #[test]
+    fn remap_clamps_out_of_range_input() {
+        assert_eq!(remap(-5.0, -1.0, 1.0, 2.0, 6.0), 2.0);
+        assert_eq!(remap(5.0, -1.0, 1.0, 2.0, 6.0), 6.0);
+    }
+
+    #[test]
+    fn noise_field_stays_within_velocity_bounds() {
+        let (v_min, v_max) = (1.5, 4.5);
+        let field = noise_field(16, 16, 42, 0.05, 4, v_min, v_max);
+
+        assert_eq!(field.dim(), (16, 16));
+        for &v in field.iter() {
+            assert!((v_min..=v_max).contains(&v), "{v} outside bounds");
+        }
+    }
+}